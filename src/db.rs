@@ -1,10 +1,17 @@
 //! RPM database access
 //!
-//! The database used is whichever one is configured as the `_dbpath` in the
-//! in the global macro context. By default this is unset: you will need to
-//! call `librpm::config::read_file(None)` to read the default "rpmrc"
+//! The free functions in this module (`find`, `installed_packages`) query
+//! whichever database is configured as the `_dbpath` in the global macro
+//! context. By default this is unset: you will need to call
+//! `librpm::config::read_file(None)` to read the default "rpmrc"
 //! configuration.
 //!
+//! To query a database other than the global one — a chroot, a mounted
+//! system image, or an alternate `_dbpath` — open a [`Db`] instead, which
+//! owns its own transaction set. Its root directory is set on that
+//! transaction set alone; a custom `_dbpath` is not: it is written to the
+//! global macro context while the `Db` is alive (see [`Db`]).
+//!
 //! # Example
 //!
 //! Finding the "rpm-devel" RPM in the database:
@@ -21,91 +28,170 @@
 //! println!("package summary: {}", package.summary);
 //! println!("package version: {}", package.version);
 //! ```
+//!
+//! Inspecting a database mounted at `/mnt/sysimage` instead:
+//!
+//! ```no_run
+//! use librpm::{Db, Index};
+//!
+//! let db = Db::open_with()
+//!     .with_root("/mnt/sysimage")
+//!     .with_dbpath("/var/lib/rpm")
+//!     .open()
+//!     .unwrap();
+//!
+//! for package in db.find(Index::Name, "rpm-devel") {
+//!     println!("package name: {}", package.name);
+//! }
+//! ```
 
 use crate::error::{Error, ErrorKind};
 use crate::internal::{iterator::MatchIterator, tag::Tag};
 use crate::package::Package;
 use streaming_iterator::StreamingIterator;
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_void;
 use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::Arc;
 
-struct Db {}
+/// An open handle to an RPM database.
+///
+/// Unlike the free functions in this module, which always query the
+/// single global, macro-configured database, a `Db` owns a long-lived
+/// `rpmts` transaction set of its own, optionally scoped to an alternate
+/// root directory and/or `_dbpath`. `_dbpath` itself is process-global
+/// macro state in librpm: opening a `Db` with a custom `_dbpath` saves
+/// whatever value was previously set, and dropping it restores that
+/// value (or unsets the macro entirely if there wasn't one).
+///
+/// This save/restore is only sound if `Db` handles with a custom
+/// `_dbpath` are opened and dropped in strict LIFO order. If `Db` A
+/// (custom dbpath) is opened, then `Db` B (a different dbpath) is opened
+/// while A is still alive, then A is dropped before B, A's `Drop` blindly
+/// restores `_dbpath` to whatever preceded A — stomping on B's still-live
+/// dbpath out from under it. `_dbpath` is genuinely process-global state,
+/// so this isn't fully fixable here; don't rely on a custom `_dbpath`
+/// being isolated to one handle if other `Db`s with different dbpaths may
+/// be alive at the same time.
+pub struct Db {
+    inner: Arc<DbHandle>,
+}
 
-struct DbBuilder<P>
-where
-    P: AsRef<Path>,
-{
-    config: Option<P>,
+struct DbHandle {
+    ts: librpm_sys::rpmts,
+    // The `_dbpath` value to restore on drop, if this handle overrode it.
+    // `Some(None)` means no `_dbpath` was set before we opened.
+    previous_dbpath: Option<Option<CString>>,
 }
 
-impl<P> Default for DbBuilder<P>
-where
-    P: AsRef<Path>,
-{
-    fn default() -> Self {
-        Self { config: None }
+impl Drop for DbHandle {
+    fn drop(&mut self) {
+        if let Some(ref previous) = self.previous_dbpath {
+            restore_dbpath_macro(previous);
+        }
+        unsafe {
+            librpm_sys::rpmtsFree(self.ts);
+        }
     }
 }
 
 impl Db {
-    fn open<P>() -> Result<Self, Error>
-    where
-        P: AsRef<Path>,
-    {
-        DbBuilder::<&Path>::new().open()
+    /// Open the database at its default location, reading the default RPM
+    /// configuration files.
+    pub fn open() -> Result<Self, Error> {
+        DbBuilder::new().open()
     }
 
-    fn open_with<P>() -> DbBuilder<P>
-    where
-        P: AsRef<Path>,
-    {
-        DbBuilder::default()
+    /// Begin building a `Db` with a custom root directory, `_dbpath`,
+    /// and/or configuration file.
+    pub fn open_with() -> DbBuilder {
+        DbBuilder::new()
+    }
+
+    /// Find packages in this database matching `key` in the given `index`.
+    pub fn find<S: AsRef<str>>(&self, index: Index, key: S) -> Iter {
+        Iter {
+            iter: MatchIterator::with_ts(self.inner.ts, index.into(), Some(key.as_ref())),
+            db: Some(self.inner.clone()),
+        }
+    }
+
+    /// Find all packages installed in this database.
+    pub fn installed_packages(&self) -> Iter {
+        Iter {
+            iter: MatchIterator::with_ts(self.inner.ts, Tag::NAME, None),
+            db: Some(self.inner.clone()),
+        }
+    }
+}
+
+/// Builder for a [`Db`], allowing a custom root directory, `_dbpath`, or
+/// RPM configuration file to be set before the database (and its backing
+/// transaction set) is opened.
+pub struct DbBuilder {
+    config: Option<PathBuf>,
+    root: Option<PathBuf>,
+    dbpath: Option<PathBuf>,
+}
+
+impl Default for DbBuilder {
+    fn default() -> Self {
+        Self {
+            config: None,
+            root: None,
+            dbpath: None,
+        }
     }
 }
 
-impl<P> DbBuilder<P>
-where
-    P: AsRef<Path>,
-{
+impl DbBuilder {
     fn new() -> Self {
         Self::default()
     }
 
-    fn with_config(&mut self, config: P) {
-        self.config = Some(config);
+    /// Read RPM configuration from `path` instead of the default locations.
+    pub fn with_config<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Scope the database to an alternate root directory, as `rpm --root`
+    /// does (`rpmtsSetRootDir`).
+    pub fn with_root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use an explicit `_dbpath` rather than whichever one is configured in
+    /// the global macro context. The value is not scoped to this handle:
+    /// opening sets the global `_dbpath` macro, and dropping the `Db`
+    /// restores the previous value (see [`Db`]).
+    pub fn with_dbpath<P: AsRef<Path>>(mut self, dbpath: P) -> Self {
+        self.dbpath = Some(dbpath.as_ref().to_path_buf());
+        self
     }
-    
-    fn open(self) -> Result<Db, Error> {
+
+    /// Open the database, creating its backing transaction set.
+    pub fn open(self) -> Result<Db, Error> {
         let rc = match self.config {
             Some(ref path) => {
-                if !path.as_ref().exists() {
-                    fail!(
-                        ErrorKind::Config,
-                        "no such file: {}",
-                        path.as_ref().display()
-                    )
+                if !path.exists() {
+                    fail!(ErrorKind::Config, "no such file: {}", path.display())
                 }
-                let cstr = CString::new(path.as_ref().as_os_str().as_bytes()).map_err(|e| {
-                    format_err!(
-                        ErrorKind::Config,
-                        "invalid path: {} ({})",
-                        path.as_ref().display(),
-                        e
-                    )
-                })?;
+                let cstr = path_to_cstring(path)?;
                 unsafe { librpm_sys::rpmReadConfigFiles(cstr.as_ptr(), ptr::null()) }
             }
             None => unsafe { librpm_sys::rpmReadConfigFiles(ptr::null(), ptr::null()) },
         };
         if rc != 0 {
             match self.config {
-                Some(path) => fail!(
+                Some(ref path) => fail!(
                     ErrorKind::Config,
                     "error reading RPM config from: {}",
-                    path.as_ref().display()
+                    path.display()
                 ),
                 None => fail!(
                     ErrorKind::Config,
@@ -113,19 +199,122 @@ where
                 ),
             }
         }
-        Err(Error::new(ErrorKind::Config, None))
+
+        let ts = unsafe { librpm_sys::rpmtsCreate() };
+        if ts.is_null() {
+            fail!(ErrorKind::Config, "failed to create RPM transaction set")
+        }
+
+        if let Some(ref root) = self.root {
+            let cstr = path_to_cstring(root)?;
+            let rc = unsafe { librpm_sys::rpmtsSetRootDir(ts, cstr.as_ptr()) };
+            if rc != 0 {
+                unsafe { librpm_sys::rpmtsFree(ts) };
+                fail!(
+                    ErrorKind::Config,
+                    "failed to set root directory: {}",
+                    root.display()
+                )
+            }
+        }
+
+        let previous_dbpath = if let Some(ref dbpath) = self.dbpath {
+            let previous = current_dbpath_macro();
+            if let Err(e) = set_dbpath_macro(dbpath) {
+                unsafe { librpm_sys::rpmtsFree(ts) };
+                return Err(e);
+            }
+            Some(previous)
+        } else {
+            None
+        };
+
+        Ok(Db {
+            inner: Arc::new(DbHandle { ts, previous_dbpath }),
+        })
+    }
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString, Error> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| {
+        format_err!(
+            ErrorKind::Config,
+            "invalid path: {} ({})",
+            path.display(),
+            e
+        )
+    })
+}
+
+fn set_dbpath_macro(dbpath: &Path) -> Result<(), Error> {
+    let name = CString::new("_dbpath").expect("no interior nulls");
+    let value = path_to_cstring(dbpath)?;
+    let rc = unsafe {
+        librpm_sys::addMacro(ptr::null_mut(), name.as_ptr(), ptr::null(), value.as_ptr(), -1)
+    };
+    if rc != 0 {
+        fail!(
+            ErrorKind::Config,
+            "failed to set _dbpath: {}",
+            dbpath.display()
+        )
+    }
+    Ok(())
+}
+
+/// The current value of `_dbpath`, or `None` if it is unset, via a
+/// conditional macro expansion (`%{?_dbpath}` expands to an empty string
+/// rather than failing when the macro is undefined).
+fn current_dbpath_macro() -> Option<CString> {
+    let expr = CString::new("%{?_dbpath}").expect("no interior nulls");
+    let expanded = unsafe { librpm_sys::rpmExpandMacro(ptr::null_mut(), expr.as_ptr(), ptr::null_mut(), 0) };
+    if expanded.is_null() {
+        return None;
+    }
+    let value = unsafe { CStr::from_ptr(expanded).to_owned() };
+    unsafe { librpm_sys::free(expanded as *mut c_void) };
+    if value.to_bytes().is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn restore_dbpath_macro(previous: &Option<CString>) {
+    let name = CString::new("_dbpath").expect("no interior nulls");
+    unsafe {
+        match previous {
+            Some(value) => {
+                librpm_sys::addMacro(ptr::null_mut(), name.as_ptr(), ptr::null(), value.as_ptr(), -1);
+            }
+            None => {
+                librpm_sys::delMacro(ptr::null_mut(), name.as_ptr());
+            }
+        }
     }
 }
 
 /// Iterator over the RPM database which returns `Package` structs.
-pub struct Iter(MatchIterator);
+pub struct Iter {
+    iter: MatchIterator,
+    // Keeps a `Db`'s backing transaction set alive for as long as results
+    // are still being pulled from it. `None` for iterators built against
+    // the global, macro-configured database.
+    db: Option<Arc<DbHandle>>,
+}
+
+impl Iter {
+    fn new(iter: MatchIterator) -> Self {
+        Self { iter, db: None }
+    }
+}
 
 impl Iterator for Iter {
     type Item = Package;
 
     /// Obtain the next header from the iterator.
     fn next(&mut self) -> Option<Package> {
-        self.0.next().map(|h| h.to_package())
+        self.iter.next().map(|h| h.to_package())
     }
 }
 
@@ -149,9 +338,95 @@ pub enum Index {
 }
 
 impl Index {
-    /// Find an exact match in the given index
+    /// Find an exact match in the given index, against the global
+    /// database.
     pub fn find<S: AsRef<str>>(self, key: S) -> Iter {
-        Iter(MatchIterator::new(self.into(), Some(key.as_ref())))
+        Iter::new(MatchIterator::new(self.into(), Some(key.as_ref())))
+    }
+
+    /// Find matches in the given index using a non-default comparison
+    /// mode, e.g. `Index::Name.find_with("kernel*", MatchMode::Glob)`.
+    pub fn find_with<S: AsRef<str>>(self, key: S, mode: MatchMode) -> Iter {
+        let tag = self.into();
+        let mut iter = MatchIterator::new(tag, None);
+        iter.set_match_mode(tag, mode.as_mire_mode(), key.as_ref());
+        Iter::new(iter)
+    }
+
+    /// Find matches in the given index against a POSIX extended regular
+    /// expression.
+    pub fn find_regex<S: AsRef<str>>(self, pattern: S) -> Iter {
+        self.find_with(pattern, MatchMode::Regex)
+    }
+
+    /// Find matches in the given index whose version satisfies `sense`
+    /// when compared against `version`, e.g.
+    /// `Index::Version.find_version(VersionSense::GreaterEqual, "1.2.3")`.
+    pub fn find_version<S: AsRef<str>>(self, sense: VersionSense, version: S) -> Iter {
+        let tag = self.into();
+        let mut iter = MatchIterator::new(tag, None);
+        iter.set_version_constraint(tag, sense.as_sense_flags(), version.as_ref());
+        Iter::new(iter)
+    }
+}
+
+/// Comparison modes supported by [`Index::find_with`], mirroring the
+/// match modes that `rpmdbSetIteratorRE` accepts.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MatchMode {
+    /// Exact string comparison (the default; `strcmp`).
+    Default,
+
+    /// Shell-style glob matching (`fnmatch`).
+    Glob,
+
+    /// POSIX extended regular expression matching.
+    Regex,
+}
+
+impl MatchMode {
+    fn as_mire_mode(self) -> librpm_sys::rpmMireMode {
+        match self {
+            MatchMode::Default => librpm_sys::rpmMireMode_RPMMIRE_DEFAULT,
+            MatchMode::Glob => librpm_sys::rpmMireMode_RPMMIRE_GLOB,
+            MatchMode::Regex => librpm_sys::rpmMireMode_RPMMIRE_REGEX,
+        }
+    }
+}
+
+/// Comparison operator for [`Index::find_version`], mapped to the
+/// `rpmsenseFlags` used to constrain a version match.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VersionSense {
+    /// Strictly less than.
+    Less,
+
+    /// Less than or equal to.
+    LessEqual,
+
+    /// Exactly equal to.
+    Equal,
+
+    /// Greater than or equal to.
+    GreaterEqual,
+
+    /// Strictly greater than.
+    Greater,
+}
+
+impl VersionSense {
+    fn as_sense_flags(self) -> librpm_sys::rpmsenseFlags {
+        match self {
+            VersionSense::Less => librpm_sys::rpmsenseFlags_RPMSENSE_LESS,
+            VersionSense::LessEqual => {
+                librpm_sys::rpmsenseFlags_RPMSENSE_LESS | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL
+            }
+            VersionSense::Equal => librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL,
+            VersionSense::GreaterEqual => {
+                librpm_sys::rpmsenseFlags_RPMSENSE_GREATER | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL
+            }
+            VersionSense::Greater => librpm_sys::rpmsenseFlags_RPMSENSE_GREATER,
+        }
     }
 }
 
@@ -169,7 +444,7 @@ impl Into<Tag> for Index {
 
 /// Find all packages installed on the local system.
 pub fn installed_packages() -> Iter {
-    Iter(MatchIterator::new(Tag::NAME, None))
+    Iter::new(MatchIterator::new(Tag::NAME, None))
 }
 
 /// Find installed packages with a search key that exactly matches the given tag.
@@ -179,11 +454,101 @@ pub fn find<S: AsRef<str>>(index: Index, key: S) -> Iter {
     index.find(key)
 }
 
+/// Find all installed packages that provide `capability`, by matching
+/// `capability` against the `PROVIDENAME` index.
+///
+/// ```no_run
+/// for package in librpm::providers("libssl.so.3") {
+///     println!("{} provides libssl.so.3", package.name);
+/// }
+/// ```
+pub fn providers<S: AsRef<str>>(capability: S) -> Iter {
+    Iter::new(MatchIterator::new(Tag::PROVIDENAME, Some(capability.as_ref())))
+}
+
+/// Find the installed package that owns the file at `path`, answering the
+/// `rpm -qf` question.
+///
+/// ```no_run
+/// let mut owners = librpm::owner_of("/usr/bin/foo");
+/// println!("owned by: {}", owners.next().unwrap().name);
+/// ```
+///
+/// See `Package::files`, which reconstructs a package's full file list
+/// (with mode and size) from the `DIRNAMES`/`DIRINDEXES`/`BASENAMES` tag
+/// triplet on the header, for the inverse lookup.
+pub fn owner_of<P: AsRef<Path>>(path: P) -> Iter {
+    let path = path.as_ref().to_string_lossy();
+    // `BASENAMES` entries are bare filenames (`foo`), not full paths, so a
+    // plain header-tag match against them would never see `/usr/bin/foo`.
+    // `RPMDBI_INSTFILENAMES` is the dedicated database index that accepts
+    // a full path and is what `rpm -qf` itself uses.
+    Iter::new(MatchIterator::with_dbi(
+        librpm_sys::RPMDBI_INSTFILENAMES,
+        path.as_ref(),
+    ))
+}
+
+/// A single entry in a package's `requires`, `provides`, `conflicts`, or
+/// `obsoletes` dependency set: a capability name together with the sense
+/// flags and version that constrain it.
+///
+/// See `Package::requires`, `Package::provides`, `Package::conflicts`, and
+/// `Package::obsoletes`, which build these from the `REQUIRENAME`/
+/// `REQUIREFLAGS`/`REQUIREVERSION` (and the `PROVIDE*`, `CONFLICT*`,
+/// `OBSOLETE*`) tag triplets on the package header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dependency {
+    /// The capability name, e.g. `libssl.so.3` or a package name.
+    pub name: String,
+
+    /// The sense flags qualifying `version` (`rpmsenseFlags`), e.g.
+    /// `RPMSENSE_GREATER | RPMSENSE_EQUAL` for `>=`.
+    pub sense: librpm_sys::rpmsenseFlags,
+
+    /// The version constraint, or an empty string if this dependency is
+    /// unversioned.
+    pub version: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
     #[test]
     fn db_opens() {
-        Db::open::<&Path>().unwrap();
+        Db::open().unwrap();
+    }
+
+    #[test]
+    fn owner_of_an_unowned_path_is_empty() {
+        let mut owners = owner_of("/nonexistent/path/that/surely/does/not/exist");
+        assert!(owners.next().is_none());
+    }
+
+    #[test]
+    fn match_mode_maps_each_variant() {
+        assert_eq!(MatchMode::Default.as_mire_mode(), librpm_sys::rpmMireMode_RPMMIRE_DEFAULT);
+        assert_eq!(MatchMode::Glob.as_mire_mode(), librpm_sys::rpmMireMode_RPMMIRE_GLOB);
+        assert_eq!(MatchMode::Regex.as_mire_mode(), librpm_sys::rpmMireMode_RPMMIRE_REGEX);
+    }
+
+    #[test]
+    fn version_sense_maps_strict_operators() {
+        assert_eq!(VersionSense::Less.as_sense_flags(), librpm_sys::rpmsenseFlags_RPMSENSE_LESS);
+        assert_eq!(VersionSense::Equal.as_sense_flags(), librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL);
+        assert_eq!(VersionSense::Greater.as_sense_flags(), librpm_sys::rpmsenseFlags_RPMSENSE_GREATER);
+    }
+
+    #[test]
+    fn version_sense_maps_inclusive_operators_as_combined_flags() {
+        assert_eq!(
+            VersionSense::LessEqual.as_sense_flags(),
+            librpm_sys::rpmsenseFlags_RPMSENSE_LESS | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL
+        );
+        assert_eq!(
+            VersionSense::GreaterEqual.as_sense_flags(),
+            librpm_sys::rpmsenseFlags_RPMSENSE_GREATER | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL
+        );
     }
 }
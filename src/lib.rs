@@ -0,0 +1,21 @@
+//! Safe bindings to librpm, the RPM Package Manager library.
+
+#[macro_use]
+mod error;
+
+mod internal;
+
+pub mod config;
+pub mod db;
+pub mod package;
+pub mod resolve;
+pub mod transaction;
+
+pub use crate::db::{
+    find, installed_packages, owner_of, providers, Db, DbBuilder, Dependency, Index, Iter,
+    MatchMode, VersionSense,
+};
+pub use crate::error::{Error, ErrorKind};
+pub use crate::package::{FileEntry, Package};
+pub use crate::resolve::{resolve, Capability, Edge, Resolution};
+pub use crate::transaction::{ElementResult, NotifyEvent, Outcome, Transaction};
@@ -0,0 +1,398 @@
+//! RPM package headers, as returned by the queries in [`crate::db`].
+
+use crate::db::Dependency;
+
+use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::ptr;
+
+/// A single package header, as returned by [`crate::db::Index::find`],
+/// [`crate::db::installed_packages`], and the rest of the query surface
+/// in [`crate::db`].
+///
+/// `Package` is cheap to clone: it holds a reference-counted handle to
+/// the underlying `Header`, shared (not copied) on [`Clone::clone`].
+#[derive(Clone, Debug)]
+pub struct Package {
+    header: RawHeader,
+
+    /// The package's name, e.g. `rpm-devel`.
+    pub name: String,
+
+    /// The package's version, e.g. `4.14.0`.
+    pub version: String,
+
+    /// The package's license, e.g. `GPLv2+`.
+    pub license: String,
+
+    /// A one-line summary of the package.
+    pub summary: String,
+
+    /// The package's full description.
+    pub description: String,
+}
+
+#[derive(Debug)]
+struct RawHeader(librpm_sys::Header);
+
+impl Clone for RawHeader {
+    fn clone(&self) -> Self {
+        RawHeader(unsafe { librpm_sys::headerLink(self.0) })
+    }
+}
+
+impl Drop for RawHeader {
+    fn drop(&mut self) {
+        unsafe {
+            librpm_sys::headerFree(self.0);
+        }
+    }
+}
+
+impl Package {
+    /// Wrap a raw header handle, taking ownership of the reference held
+    /// by `header`. Used by the iterator layer to convert a raw header
+    /// into an owned `Package`.
+    pub(crate) fn from_header(
+        header: librpm_sys::Header,
+        name: String,
+        version: String,
+        license: String,
+        summary: String,
+        description: String,
+    ) -> Self {
+        Self {
+            header: RawHeader(header),
+            name,
+            version,
+            license,
+            summary,
+            description,
+        }
+    }
+
+    /// The raw header handle backing this package, for APIs (like
+    /// [`crate::transaction::Transaction::add_erase`]) that need to pass
+    /// it straight through to librpm.
+    pub(crate) fn raw_header(&self) -> librpm_sys::Header {
+        self.header.0
+    }
+
+    /// This package's NEVRA (name-epoch:version-release.arch) string,
+    /// e.g. `rpm-devel-0:4.14.0-1.x86_64`, via `headerGetNEVRA`.
+    ///
+    /// Used by [`crate::resolve::resolve`] to identify a package
+    /// uniquely when deduplicating across dependency resolution.
+    pub fn nevra(&self) -> String {
+        unsafe {
+            let raw = librpm_sys::headerGetNEVRA(self.header.0, ptr::null_mut());
+            if raw.is_null() {
+                return self.name.clone();
+            }
+            let nevra = CStr::from_ptr(raw).to_string_lossy().into_owned();
+            librpm_sys::free(raw as *mut c_void);
+            nevra
+        }
+    }
+
+    /// This package's `REQUIRENAME`/`REQUIREFLAGS`/`REQUIREVERSION`
+    /// dependency set: the capabilities it needs satisfied.
+    pub fn requires(&self) -> Vec<Dependency> {
+        dependency_set(
+            self.header.0,
+            librpm_sys::rpmTag_RPMTAG_REQUIRENAME,
+            librpm_sys::rpmTag_RPMTAG_REQUIREFLAGS,
+            librpm_sys::rpmTag_RPMTAG_REQUIREVERSION,
+        )
+    }
+
+    /// This package's `PROVIDENAME`/`PROVIDEFLAGS`/`PROVIDEVERSION`
+    /// dependency set: the capabilities it offers.
+    pub fn provides(&self) -> Vec<Dependency> {
+        dependency_set(
+            self.header.0,
+            librpm_sys::rpmTag_RPMTAG_PROVIDENAME,
+            librpm_sys::rpmTag_RPMTAG_PROVIDEFLAGS,
+            librpm_sys::rpmTag_RPMTAG_PROVIDEVERSION,
+        )
+    }
+
+    /// This package's `CONFLICTNAME`/`CONFLICTFLAGS`/`CONFLICTVERSION`
+    /// dependency set: the capabilities it cannot coexist with.
+    pub fn conflicts(&self) -> Vec<Dependency> {
+        dependency_set(
+            self.header.0,
+            librpm_sys::rpmTag_RPMTAG_CONFLICTNAME,
+            librpm_sys::rpmTag_RPMTAG_CONFLICTFLAGS,
+            librpm_sys::rpmTag_RPMTAG_CONFLICTVERSION,
+        )
+    }
+
+    /// This package's `OBSOLETENAME`/`OBSOLETEFLAGS`/`OBSOLETEVERSION`
+    /// dependency set: the capabilities it supersedes.
+    pub fn obsoletes(&self) -> Vec<Dependency> {
+        dependency_set(
+            self.header.0,
+            librpm_sys::rpmTag_RPMTAG_OBSOLETENAME,
+            librpm_sys::rpmTag_RPMTAG_OBSOLETEFLAGS,
+            librpm_sys::rpmTag_RPMTAG_OBSOLETEVERSION,
+        )
+    }
+
+    /// This package's installed files, reconstructed from the
+    /// `DIRNAMES`/`DIRINDEXES`/`BASENAMES` tag triplet together with
+    /// their `FILEMODES`/`FILESIZES`. The inverse of
+    /// [`crate::db::owner_of`].
+    pub fn files(&self) -> Vec<FileEntry> {
+        let dirnames = tag_strings(self.header.0, librpm_sys::rpmTag_RPMTAG_DIRNAMES);
+        let dirindexes = tag_uint32s(self.header.0, librpm_sys::rpmTag_RPMTAG_DIRINDEXES);
+        let basenames = tag_strings(self.header.0, librpm_sys::rpmTag_RPMTAG_BASENAMES);
+        let modes = tag_uint32s(self.header.0, librpm_sys::rpmTag_RPMTAG_FILEMODES);
+        let sizes = tag_uint32s(self.header.0, librpm_sys::rpmTag_RPMTAG_FILESIZES);
+
+        basenames
+            .into_iter()
+            .enumerate()
+            .map(|(i, basename)| {
+                let dir = dirindexes
+                    .get(i)
+                    .and_then(|&idx| dirnames.get(idx as usize))
+                    .cloned()
+                    .unwrap_or_default();
+                FileEntry {
+                    path: format!("{}{}", dir, basename),
+                    mode: modes.get(i).copied().unwrap_or(0) as u16,
+                    size: sizes.get(i).copied().unwrap_or(0) as u64,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A single installed file from [`Package::files`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileEntry {
+    /// The file's full installed path, e.g. `/usr/bin/foo`.
+    pub path: String,
+    /// The file's mode bits (`st_mode`), from `FILEMODES`.
+    pub mode: u16,
+    /// The file's size in bytes, from `FILESIZES`.
+    pub size: u64,
+}
+
+/// Read a `*NAME`/`*FLAGS`/`*VERSION` tag triplet off `header` into a set
+/// of [`Dependency`] entries.
+fn dependency_set(
+    header: librpm_sys::Header,
+    name_tag: librpm_sys::rpmTag,
+    flags_tag: librpm_sys::rpmTag,
+    version_tag: librpm_sys::rpmTag,
+) -> Vec<Dependency> {
+    let names = tag_strings(header, name_tag);
+    let flags = tag_uint32s(header, flags_tag);
+    let versions = tag_strings(header, version_tag);
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| Dependency {
+            name,
+            sense: flags.get(i).copied().unwrap_or(0) as librpm_sys::rpmsenseFlags,
+            version: versions.get(i).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn tag_strings(header: librpm_sys::Header, tag: librpm_sys::rpmTag) -> Vec<String> {
+    let mut td: librpm_sys::rpmtd_s = unsafe { std::mem::zeroed() };
+    let mut values = Vec::new();
+
+    unsafe {
+        if librpm_sys::headerGet(header, tag, &mut td, librpm_sys::headerGetFlags_HEADERGET_EXT) != 0 {
+            while librpm_sys::rpmtdNext(&mut td) >= 0 {
+                let ptr = librpm_sys::rpmtdGetString(&mut td);
+                if !ptr.is_null() {
+                    values.push(CStr::from_ptr(ptr).to_string_lossy().into_owned());
+                }
+            }
+            librpm_sys::rpmtdFreeData(&mut td);
+        }
+    }
+
+    values
+}
+
+fn tag_uint32s(header: librpm_sys::Header, tag: librpm_sys::rpmTag) -> Vec<u32> {
+    let mut td: librpm_sys::rpmtd_s = unsafe { std::mem::zeroed() };
+    let mut values = Vec::new();
+
+    unsafe {
+        if librpm_sys::headerGet(header, tag, &mut td, librpm_sys::headerGetFlags_HEADERGET_EXT) != 0 {
+            while librpm_sys::rpmtdNext(&mut td) >= 0 {
+                let ptr = librpm_sys::rpmtdGetUint32(&mut td);
+                if !ptr.is_null() {
+                    values.push(*ptr);
+                }
+            }
+            librpm_sys::rpmtdFreeData(&mut td);
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::os::raw::c_int;
+
+    /// Build a standalone `Package` with a single `REQUIRENAME`/
+    /// `REQUIREFLAGS`/`REQUIREVERSION` dependency entry, for exercising
+    /// `dependency_set` without a real database.
+    fn package_with_requires(name: &str, version: &str, sense: librpm_sys::rpmsenseFlags) -> Package {
+        unsafe {
+            let header = librpm_sys::headerNew();
+
+            let name_c = CString::new(name).expect("no interior nulls");
+            let names = [name_c.as_ptr()];
+            librpm_sys::headerPutStringArray(
+                header,
+                librpm_sys::rpmTag_RPMTAG_REQUIRENAME,
+                names.as_ptr(),
+                names.len() as c_int,
+            );
+
+            let version_c = CString::new(version).expect("no interior nulls");
+            let versions = [version_c.as_ptr()];
+            librpm_sys::headerPutStringArray(
+                header,
+                librpm_sys::rpmTag_RPMTAG_REQUIREVERSION,
+                versions.as_ptr(),
+                versions.len() as c_int,
+            );
+
+            let flags = [sense];
+            librpm_sys::headerPutUint32Array(
+                header,
+                librpm_sys::rpmTag_RPMTAG_REQUIREFLAGS,
+                flags.as_ptr(),
+                flags.len() as c_int,
+            );
+
+            Package::from_header(
+                header,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+            )
+        }
+    }
+
+    #[test]
+    fn requires_reads_back_the_name_flags_and_version_triplet() {
+        let package = package_with_requires(
+            "libssl.so.3",
+            "1.2.3",
+            librpm_sys::rpmsenseFlags_RPMSENSE_GREATER | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL,
+        );
+
+        let requires = package.requires();
+        assert_eq!(requires.len(), 1);
+        assert_eq!(requires[0].name, "libssl.so.3");
+        assert_eq!(requires[0].version, "1.2.3");
+        assert_eq!(
+            requires[0].sense,
+            librpm_sys::rpmsenseFlags_RPMSENSE_GREATER | librpm_sys::rpmsenseFlags_RPMSENSE_EQUAL
+        );
+    }
+
+    #[test]
+    fn provides_conflicts_and_obsoletes_are_empty_without_their_tags() {
+        let package = package_with_requires("whatever", "", 0);
+        assert!(package.provides().is_empty());
+        assert!(package.conflicts().is_empty());
+        assert!(package.obsoletes().is_empty());
+    }
+
+    #[test]
+    fn files_reconstructs_paths_from_the_dirname_dirindex_basename_triplet() {
+        unsafe {
+            let header = librpm_sys::headerNew();
+
+            let dirs = [
+                CString::new("/usr/bin/").expect("no interior nulls"),
+                CString::new("/etc/").expect("no interior nulls"),
+            ];
+            let dir_ptrs: Vec<_> = dirs.iter().map(|d| d.as_ptr()).collect();
+            librpm_sys::headerPutStringArray(
+                header,
+                librpm_sys::rpmTag_RPMTAG_DIRNAMES,
+                dir_ptrs.as_ptr(),
+                dir_ptrs.len() as c_int,
+            );
+
+            let basenames = [
+                CString::new("foo").expect("no interior nulls"),
+                CString::new("foo.conf").expect("no interior nulls"),
+            ];
+            let basename_ptrs: Vec<_> = basenames.iter().map(|b| b.as_ptr()).collect();
+            librpm_sys::headerPutStringArray(
+                header,
+                librpm_sys::rpmTag_RPMTAG_BASENAMES,
+                basename_ptrs.as_ptr(),
+                basename_ptrs.len() as c_int,
+            );
+
+            let dirindexes: [u32; 2] = [0, 1];
+            librpm_sys::headerPutUint32Array(
+                header,
+                librpm_sys::rpmTag_RPMTAG_DIRINDEXES,
+                dirindexes.as_ptr(),
+                dirindexes.len() as c_int,
+            );
+
+            let modes: [u32; 2] = [0o100755, 0o100644];
+            librpm_sys::headerPutUint32Array(
+                header,
+                librpm_sys::rpmTag_RPMTAG_FILEMODES,
+                modes.as_ptr(),
+                modes.len() as c_int,
+            );
+
+            let sizes: [u32; 2] = [1024, 64];
+            librpm_sys::headerPutUint32Array(
+                header,
+                librpm_sys::rpmTag_RPMTAG_FILESIZES,
+                sizes.as_ptr(),
+                sizes.len() as c_int,
+            );
+
+            let package = Package::from_header(
+                header,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+            );
+
+            let files = package.files();
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].path, "/usr/bin/foo");
+            assert_eq!(files[0].mode, 0o100755);
+            assert_eq!(files[0].size, 1024);
+            assert_eq!(files[1].path, "/etc/foo.conf");
+            assert_eq!(files[1].mode, 0o100644);
+            assert_eq!(files[1].size, 64);
+        }
+    }
+
+    #[test]
+    fn files_is_empty_without_file_tags() {
+        let package = package_with_requires("whatever", "", 0);
+        assert!(package.files().is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! RPM transactions: installing and erasing packages.
+//!
+//! Where [`crate::db`] is read-only, a [`Transaction`] drives the same
+//! `rpmts` machinery librpm itself uses to mutate the system: read a
+//! package file, queue it for install or erase, check and order the
+//! resulting dependency graph, and run the transaction.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use librpm::Transaction;
+//!
+//! let mut ts = Transaction::new().unwrap();
+//! ts.add_install("/tmp/rpm-devel-4.14.0.rpm", false).unwrap();
+//! ts.check().unwrap();
+//! ts.order().unwrap();
+//!
+//! for result in ts.run().unwrap() {
+//!     println!("{}: {:?}", result.name, result.outcome);
+//! }
+//! ```
+
+use crate::error::{Error, ErrorKind};
+use crate::package::Package;
+
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_int, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+/// An open RPM transaction, wrapping an `rpmts` transaction set.
+///
+/// Queue one or more packages with [`Transaction::add_install`] or
+/// [`Transaction::add_erase`], resolve ordering with [`Transaction::check`]
+/// and [`Transaction::order`], then apply them with [`Transaction::run`].
+pub struct Transaction {
+    ts: librpm_sys::rpmts,
+    // Double-boxed so the value handed to librpm as opaque user data is a
+    // thin `*mut Box<NotifyCallback>` rather than a fat trait-object
+    // pointer — `*mut dyn FnMut(..)` can't be reconstructed from a plain
+    // `*mut c_void` round trip, since that throws away the vtable half.
+    callback: Option<Box<Box<NotifyCallback>>>,
+}
+
+type NotifyCallback = dyn FnMut(NotifyEvent) + 'static;
+
+impl Transaction {
+    /// Create a new, empty transaction set against the global database.
+    pub fn new() -> Result<Self, Error> {
+        let ts = unsafe { librpm_sys::rpmtsCreate() };
+        if ts.is_null() {
+            fail!(ErrorKind::Config, "failed to create RPM transaction set")
+        }
+        Ok(Self { ts, callback: None })
+    }
+
+    /// Queue the package at `package_path` for installation.
+    ///
+    /// If `upgrade` is `true`, any installed packages this one replaces
+    /// are queued for removal as part of the same transaction.
+    pub fn add_install<P: AsRef<Path>>(&mut self, package_path: P, upgrade: bool) -> Result<(), Error> {
+        let path = package_path.as_ref();
+        let cstr = CString::new(path.as_os_str().as_bytes()).map_err(|e| {
+            format_err!(ErrorKind::Config, "invalid path: {} ({})", path.display(), e)
+        })?;
+
+        let mode = CString::new("r.ufdio").expect("no interior nulls");
+        let fd = unsafe { librpm_sys::Fopen(cstr.as_ptr(), mode.as_ptr()) };
+        if fd.is_null() {
+            fail!(ErrorKind::Config, "could not open package file: {}", path.display())
+        }
+
+        let mut header: librpm_sys::Header = ptr::null_mut();
+        let rc = unsafe {
+            librpm_sys::rpmReadPackageFile(self.ts, fd, cstr.as_ptr(), &mut header as *mut _)
+        };
+        unsafe { librpm_sys::Fclose(fd) };
+
+        if rc != librpm_sys::rpmRC_e_RPMRC_OK {
+            fail!(ErrorKind::Config, "failed to read package file: {}", path.display())
+        }
+
+        let rc = unsafe {
+            librpm_sys::rpmtsAddInstallElement(
+                self.ts,
+                header,
+                cstr.as_ptr() as *const c_void,
+                if upgrade { 1 } else { 0 },
+                ptr::null(),
+            )
+        };
+        unsafe { librpm_sys::headerFree(header) };
+
+        if rc != 0 {
+            fail!(ErrorKind::Config, "failed to queue install of: {}", path.display())
+        }
+        Ok(())
+    }
+
+    /// Queue an installed package for removal.
+    pub fn add_erase(&mut self, package: &Package) -> Result<(), Error> {
+        let rc = unsafe { librpm_sys::rpmtsAddEraseElement(self.ts, package.raw_header(), -1) };
+        if rc != 0 {
+            fail!(ErrorKind::Config, "failed to queue erase of: {}", package.name)
+        }
+        Ok(())
+    }
+
+    /// Check that the queued elements' dependencies are satisfiable.
+    pub fn check(&mut self) -> Result<(), Error> {
+        let rc = unsafe { librpm_sys::rpmtsCheck(self.ts) };
+        if rc != 0 {
+            fail!(ErrorKind::Config, "dependency check failed")
+        }
+        Ok(())
+    }
+
+    /// Order the queued elements so installs and erasures happen in a
+    /// dependency-safe sequence.
+    pub fn order(&mut self) -> Result<(), Error> {
+        let rc = unsafe { librpm_sys::rpmtsOrder(self.ts) };
+        if rc != 0 {
+            fail!(ErrorKind::Config, "failed to order transaction")
+        }
+        Ok(())
+    }
+
+    /// Register a callback invoked with progress events as the
+    /// transaction runs (`rpmtsSetNotifyCallback`).
+    pub fn set_notify_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(NotifyEvent) + 'static,
+    {
+        let boxed: Box<NotifyCallback> = Box::new(callback);
+        let boxed_box: Box<Box<NotifyCallback>> = Box::new(boxed);
+        // Take the address of the (thin) outer box before handing off
+        // ownership to `self.callback`; moving a `Box` moves the pointer
+        // value, not the heap allocation it points at, so this stays valid.
+        let data = &*boxed_box as *const Box<NotifyCallback> as *mut c_void;
+        self.callback = Some(boxed_box);
+        unsafe {
+            librpm_sys::rpmtsSetNotifyCallback(self.ts, Some(notify_trampoline), data);
+        }
+    }
+
+    /// Apply the ordered transaction (`rpmtsRun`), returning a result for
+    /// each queued element.
+    pub fn run(&mut self) -> Result<Vec<ElementResult>, Error> {
+        let rc = unsafe { librpm_sys::rpmtsRun(self.ts, ptr::null_mut(), 0) };
+        if rc < 0 {
+            fail!(ErrorKind::Config, "transaction failed to run")
+        }
+
+        // A positive `rc` means librpm recorded one or more per-element
+        // problems rather than a hard failure; collect which packages
+        // they belong to so we can report a `Failed` outcome for them
+        // below.
+        let mut failed = HashSet::new();
+        if rc > 0 {
+            collect_failed_elements(self.ts, &mut failed);
+        }
+
+        Ok(queued_elements(self.ts)
+            .into_iter()
+            .map(|name| {
+                let outcome = if failed.contains(&name) {
+                    Outcome::Failed
+                } else {
+                    Outcome::Ok
+                };
+                ElementResult { name, outcome }
+            })
+            .collect())
+    }
+}
+
+/// Walk `ts`'s problem set, recording the NEVR of every package a
+/// problem was reported against.
+fn collect_failed_elements(ts: librpm_sys::rpmts, failed: &mut HashSet<String>) {
+    let problems = unsafe { librpm_sys::rpmtsProblems(ts) };
+    if problems.is_null() {
+        return;
+    }
+
+    let iter = unsafe { librpm_sys::rpmpsInitIterator(problems) };
+    if !iter.is_null() {
+        while unsafe { librpm_sys::rpmpsNextIterator(iter) } >= 0 {
+            let problem = unsafe { librpm_sys::rpmpsGetProblem(iter) };
+            let pkg_nevr = unsafe { librpm_sys::rpmProblemGetPkgNEVR(problem) };
+            if !pkg_nevr.is_null() {
+                failed.insert(unsafe { CStr::from_ptr(pkg_nevr).to_string_lossy().into_owned() });
+            }
+        }
+        unsafe { librpm_sys::rpmpsFreeIterator(iter) };
+    }
+    unsafe { librpm_sys::rpmpsFree(problems) };
+}
+
+/// The NEVR of every element queued in `ts`, in transaction order.
+fn queued_elements(ts: librpm_sys::rpmts) -> Vec<String> {
+    let mut names = Vec::new();
+    let iter = unsafe { librpm_sys::rpmtsiInit(ts) };
+    loop {
+        let te = unsafe { librpm_sys::rpmtsiNext(iter, 0) };
+        if te.is_null() {
+            break;
+        }
+        names.push(element_nevr(te));
+    }
+    unsafe { librpm_sys::rpmtsiFree(iter) };
+    names
+}
+
+/// The package NEVR backing transaction element `te`, via `rpmteNEVR`.
+fn element_nevr(te: librpm_sys::rpmte) -> String {
+    let nevr = unsafe { librpm_sys::rpmteNEVR(te) };
+    if nevr.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(nevr).to_string_lossy().into_owned() }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        unsafe {
+            librpm_sys::rpmtsFree(self.ts);
+        }
+    }
+}
+
+/// A progress event reported by a [`Transaction`]'s notify callback.
+#[derive(Clone, Debug)]
+pub enum NotifyEvent {
+    /// An install or erase element started.
+    Start {
+        /// NEVR of the package being installed or erased.
+        name: String,
+    },
+    /// An install or erase element made progress, in bytes out of `total`.
+    Progress {
+        /// NEVR of the package being installed or erased.
+        name: String,
+        /// Bytes processed so far.
+        amount: u64,
+        /// Total bytes expected.
+        total: u64,
+    },
+    /// An install or erase element finished.
+    Finished {
+        /// NEVR of the package being installed or erased.
+        name: String,
+    },
+}
+
+/// The outcome of a single queued element once [`Transaction::run`]
+/// completes.
+#[derive(Clone, Debug)]
+pub struct ElementResult {
+    /// NEVR of the package this element installed or erased.
+    pub name: String,
+    /// Whether the element succeeded.
+    pub outcome: Outcome,
+}
+
+/// Whether a queued transaction element succeeded or failed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The element was applied successfully.
+    Ok,
+    /// The element failed; see the transaction's problem set for why.
+    Failed,
+}
+
+/// Map an `rpmCallbackType` plus its accompanying name/amount/total into
+/// a [`NotifyEvent`], or `None` for callback types this API doesn't
+/// surface (transaction start/stop, scriptlet output, and the like).
+fn decode_event(name: String, event: c_int, amount: u64, total: u64) -> Option<NotifyEvent> {
+    match event as u32 {
+        librpm_sys::rpmCallbackType_RPMCALLBACK_INST_START
+        | librpm_sys::rpmCallbackType_RPMCALLBACK_UNINST_START => Some(NotifyEvent::Start { name }),
+        librpm_sys::rpmCallbackType_RPMCALLBACK_INST_PROGRESS
+        | librpm_sys::rpmCallbackType_RPMCALLBACK_UNINST_PROGRESS => {
+            Some(NotifyEvent::Progress { name, amount, total })
+        }
+        librpm_sys::rpmCallbackType_RPMCALLBACK_INST_STOP
+        | librpm_sys::rpmCallbackType_RPMCALLBACK_UNINST_STOP => Some(NotifyEvent::Finished { name }),
+        _ => None,
+    }
+}
+
+extern "C" fn notify_trampoline(
+    te: librpm_sys::rpmte,
+    event: c_int,
+    amount: u64,
+    total: u64,
+    _key: *const c_void,
+    data: *mut c_void,
+) -> *mut c_void {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    let notify_event = match decode_event(element_nevr(te), event, amount, total) {
+        Some(notify_event) => notify_event,
+        None => return ptr::null_mut(),
+    };
+
+    let callback = unsafe { &mut **(data as *mut Box<NotifyCallback>) };
+    callback(notify_event);
+    ptr::null_mut()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_event_maps_install_and_erase_callbacks() {
+        assert!(matches!(
+            decode_event(
+                "foo".to_string(),
+                librpm_sys::rpmCallbackType_RPMCALLBACK_INST_START as c_int,
+                0,
+                0,
+            ),
+            Some(NotifyEvent::Start { name }) if name == "foo"
+        ));
+
+        assert!(matches!(
+            decode_event(
+                "foo".to_string(),
+                librpm_sys::rpmCallbackType_RPMCALLBACK_UNINST_PROGRESS as c_int,
+                5,
+                10,
+            ),
+            Some(NotifyEvent::Progress { name, amount: 5, total: 10 }) if name == "foo"
+        ));
+
+        assert!(matches!(
+            decode_event(
+                "foo".to_string(),
+                librpm_sys::rpmCallbackType_RPMCALLBACK_INST_STOP as c_int,
+                0,
+                0,
+            ),
+            Some(NotifyEvent::Finished { name }) if name == "foo"
+        ));
+    }
+
+    #[test]
+    fn decode_event_drops_callback_types_it_does_not_surface() {
+        assert!(decode_event(
+            "foo".to_string(),
+            librpm_sys::rpmCallbackType_RPMCALLBACK_TRANS_START as c_int,
+            0,
+            0,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn element_nevr_of_a_null_element_is_empty() {
+        assert_eq!(element_nevr(ptr::null_mut()), "");
+    }
+
+    #[test]
+    fn queued_elements_of_a_fresh_transaction_is_empty() {
+        let ts = unsafe { librpm_sys::rpmtsCreate() };
+        assert!(queued_elements(ts).is_empty());
+        unsafe { librpm_sys::rpmtsFree(ts) };
+    }
+
+    #[test]
+    fn collect_failed_elements_of_a_problem_free_transaction_is_empty() {
+        let ts = unsafe { librpm_sys::rpmtsCreate() };
+        let mut failed = HashSet::new();
+        collect_failed_elements(ts, &mut failed);
+        assert!(failed.is_empty());
+        unsafe { librpm_sys::rpmtsFree(ts) };
+    }
+}